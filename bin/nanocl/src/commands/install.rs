@@ -0,0 +1,28 @@
+use nanocl_error::io::IoResult;
+
+use crate::models::InstallOpts;
+use crate::utils::installer::{get_template, TemplateSource};
+
+/// Function that execute when running `nanocl install`
+pub async fn exec_install(opts: &InstallOpts) -> IoResult<()> {
+  let installer = get_template(
+    opts.template.clone(),
+    opts.sha256.clone(),
+    opts.offline,
+    !opts.skip_verify,
+  )
+  .await?;
+  match &installer.source {
+    TemplateSource::Local(path) => {
+      log::info!("Using local installer template {path}");
+    }
+    TemplateSource::Cache(path) => {
+      log::info!("Using cached installer template {path} (offline)");
+    }
+    TemplateSource::Network(url) => {
+      log::info!("Using installer template fetched from {url}");
+    }
+  }
+  println!("{}", installer.content);
+  Ok(())
+}
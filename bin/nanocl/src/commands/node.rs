@@ -1,11 +1,13 @@
+use futures_util::StreamExt;
+
 use nanocl_error::io::IoResult;
 
 use crate::{
   config::CliConfig,
-  models::{NodeArg, NodeCommand, NodeRow},
+  models::{NodeArg, NodeCommand, NodeListOpts, NodeRow},
 };
 
-use super::{GenericCommand, GenericCommandLs};
+use super::GenericCommand;
 
 impl GenericCommand for NodeArg {
   fn object_name() -> &'static str {
@@ -13,20 +15,50 @@ impl GenericCommand for NodeArg {
   }
 }
 
-impl GenericCommandLs for NodeArg {
-  type Item = NodeRow;
-  type Args = NodeArg;
-  type ApiItem = nanocld_client::stubs::node::Node;
+/// Function that execute when running `nanocl node ls`
+async fn exec_node_ls(
+  cli_conf: &CliConfig,
+  opts: &NodeListOpts,
+) -> IoResult<()> {
+  let client = &cli_conf.client;
+  let nodes = client.list_node(opts.status.as_deref()).await?;
+  let rows = nodes.into_iter().map(NodeRow::from).collect::<Vec<_>>();
+  println!("{:<20}{:<20}{:<15}", "NAME", "IP ADDRESS", "STATUS");
+  for row in rows {
+    println!("{:<20}{:<20}{:<15}", row.name, row.ip_address, row.status);
+  }
+  Ok(())
+}
 
-  fn get_key(item: &Self::Item) -> String {
-    item.name.clone()
+/// Function that execute when running `nanocl node inspect <name>`
+async fn exec_node_inspect(
+  cli_conf: &CliConfig,
+  name: &str,
+) -> IoResult<()> {
+  let client = &cli_conf.client;
+  let node = client.inspect_node(name).await?;
+  println!("{}", serde_yaml::to_string(&node)?);
+  Ok(())
+}
+
+/// Function that execute when running `nanocl node watch`
+async fn exec_node_watch(cli_conf: &CliConfig) -> IoResult<()> {
+  let client = &cli_conf.client;
+  let mut stream = client.watch_node_events().await?;
+  while let Some(event) = stream.next().await {
+    let event = event?;
+    println!("{}", serde_yaml::to_string(&event)?);
   }
+  Ok(())
 }
 
 /// Function that execute when running `nanocl node`
 pub async fn exec_node(cli_conf: &CliConfig, args: &NodeArg) -> IoResult<()> {
-  let client = &cli_conf.client;
   match &args.command {
-    NodeCommand::List(opts) => NodeArg::exec_ls(client, args, opts).await,
+    NodeCommand::List(opts) => exec_node_ls(cli_conf, opts).await,
+    NodeCommand::Inspect(opts) => {
+      exec_node_inspect(cli_conf, &opts.name).await
+    }
+    NodeCommand::Watch => exec_node_watch(cli_conf).await,
   }
 }
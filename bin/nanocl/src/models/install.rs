@@ -0,0 +1,18 @@
+use clap::Parser;
+
+/// `nanocl install` options
+#[derive(Clone, Debug, Parser)]
+pub struct InstallOpts {
+  /// Path to a local installer template, skips the network entirely
+  #[clap(long)]
+  pub template: Option<String>,
+  /// Expected sha256 checksum to verify the network template against
+  #[clap(long)]
+  pub sha256: Option<String>,
+  /// Use the last cached template instead of reaching out to the network
+  #[clap(long)]
+  pub offline: bool,
+  /// Skip integrity verification of the network template
+  #[clap(long)]
+  pub skip_verify: bool,
+}
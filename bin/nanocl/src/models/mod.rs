@@ -0,0 +1,5 @@
+mod install;
+mod node;
+
+pub use install::*;
+pub use node::*;
@@ -0,0 +1,53 @@
+use clap::{Parser, Subcommand};
+
+/// `nanocl node` and its subcommands
+#[derive(Clone, Debug, Parser)]
+pub struct NodeArg {
+  #[clap(subcommand)]
+  pub command: NodeCommand,
+}
+
+/// `nanocl node` available subcommands
+#[derive(Clone, Debug, Subcommand)]
+pub enum NodeCommand {
+  /// List nodes in the cluster
+  #[clap(alias("ls"))]
+  List(NodeListOpts),
+  /// Show detailed information about a node
+  Inspect(NodeInspectOpts),
+  /// Stream node liveness events as they happen
+  Watch,
+}
+
+/// `nanocl node ls` options
+#[derive(Clone, Debug, Parser)]
+pub struct NodeListOpts {
+  /// Only show nodes with the given status (`online` | `unreachable`)
+  #[clap(long)]
+  pub status: Option<String>,
+}
+
+/// `nanocl node inspect` options
+#[derive(Clone, Debug, Parser)]
+pub struct NodeInspectOpts {
+  /// Name of the node to inspect
+  pub name: String,
+}
+
+/// A row of `nanocl node ls`'s output table
+#[derive(Clone, Debug)]
+pub struct NodeRow {
+  pub name: String,
+  pub ip_address: String,
+  pub status: String,
+}
+
+impl From<nanocld_client::stubs::node::Node> for NodeRow {
+  fn from(node: nanocld_client::stubs::node::Node) -> Self {
+    Self {
+      name: node.name,
+      ip_address: node.ip_address,
+      status: node.status.to_string(),
+    }
+  }
+}
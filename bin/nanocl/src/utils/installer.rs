@@ -1,15 +1,54 @@
 use ntex::http;
+use sha2::{Digest, Sha256};
 
 use nanocl_error::http::HttpError;
 use nanocl_error::http_client::{HttpClientError, HttpClientResult};
-use nanocl_error::io::{FromIo, IoResult};
+use nanocl_error::io::{FromIo, IoError, IoResult};
 
 use crate::version::{CHANNEL, VERSION};
 
+/// Where the installer template ultimately came from, so the caller can
+/// report provenance to the user.
+#[derive(Clone, Debug)]
+pub enum TemplateSource {
+  /// Fetched fresh from the network
+  Network(String),
+  /// Served from the local verified-template cache
+  Cache(String),
+  /// Read from a local file passed explicitly by the user
+  Local(String),
+}
+
+/// Result of resolving the installer template: its content plus where it came from
+#[derive(Clone, Debug)]
+pub struct InstallerTemplate {
+  pub content: String,
+  pub source: TemplateSource,
+}
+
+/// Path to the `installer.yml` for the current channel/version in the release repo
+fn template_url() -> String {
+  format!("https://raw.githubusercontent.com/next-hat/nanocl/release/{CHANNEL}/bin/nanocl/{VERSION}/installer.yml")
+}
+
+/// Path to the sibling checksum file published next to `installer.yml`
+fn template_sha256_url() -> String {
+  format!("{}.sha256", template_url())
+}
+
+/// Path under the user's cache dir where the last verified template is stored
+fn cache_path() -> IoResult<std::path::PathBuf> {
+  let cache_dir = dirs::cache_dir().ok_or_else(|| {
+    IoError::not_found("Installer cache", "unable to resolve cache dir")
+  })?;
+  Ok(cache_dir.join("nanocl").join(format!("installer-{CHANNEL}-{VERSION}.yml")))
+}
+
+/// ## Get
+///
 /// Get template from our GitHub repo for installation
-async fn get() -> HttpClientResult<String> {
+async fn get(url: &str) -> HttpClientResult<String> {
   let client = http::client::Client::new();
-  let url = format!("https://raw.githubusercontent.com/next-hat/nanocl/release/{CHANNEL}/bin/nanocl/{VERSION}/installer.yml");
   let mut res = client.get(url).send().await.map_err(|err| {
     err.map_err_context(|| "Unable to fetch installer template")
   })?;
@@ -29,19 +68,157 @@ async fn get() -> HttpClientResult<String> {
   Ok(body)
 }
 
-/// Get template from our GitHub repo or from the specified file if it's provided
-pub async fn get_template(template: Option<String>) -> IoResult<String> {
-  match template {
-    None => {
-      let template = get().await?;
-      Ok(template)
+/// ## Verify digest
+///
+/// Verify that a template's SHA-256 digest matches the expected one.
+///
+/// ## Arguments
+///
+/// * [content](str) The fetched template content
+/// * [expected](str) The expected hex-encoded SHA-256 digest
+///
+/// ## Returns
+///
+/// * [Result](Result) Result of the operation
+///   * [Ok](()) - The digest matches
+///   * [Err](IoError) - The digest does not match
+///
+fn verify_digest(content: &str, expected: &str) -> IoResult<()> {
+  let mut hasher = Sha256::new();
+  hasher.update(content.as_bytes());
+  let digest = format!("{:x}", hasher.finalize());
+  let expected = expected.trim().to_lowercase();
+  if digest != expected {
+    return Err(IoError::invalid_data(
+      "Installer template",
+      &format!(
+        "checksum mismatch, expected {expected} but got {digest}"
+      ),
+    ));
+  }
+  Ok(())
+}
+
+/// ## Get cached template
+///
+/// Read the last successfully verified template from the local cache.
+///
+/// ## Returns
+///
+/// * [Result](Result) Result of the operation
+///   * [Ok](InstallerTemplate) - The cached template
+///   * [Err](IoError) - No cached template is available
+///
+fn get_cached_template() -> IoResult<InstallerTemplate> {
+  let path = cache_path()?;
+  let content = std::fs::read_to_string(&path).map_err(|err| {
+    IoError::not_found(
+      "Installer cache",
+      &format!("no cached template available {err}"),
+    )
+  })?;
+  Ok(InstallerTemplate {
+    content,
+    source: TemplateSource::Cache(path.display().to_string()),
+  })
+}
+
+/// ## Cache template
+///
+/// Persist a successfully verified template under the user's cache dir, so
+/// a later install can proceed offline with `--offline`.
+///
+/// ## Arguments
+///
+/// * [content](str) The verified template content
+///
+fn cache_template(content: &str) {
+  let path = match cache_path() {
+    Ok(path) => path,
+    Err(err) => {
+      log::warn!("Unable to resolve installer cache path: {err}");
+      return;
+    }
+  };
+  if let Some(parent) = path.parent() {
+    if let Err(err) = std::fs::create_dir_all(parent) {
+      log::warn!("Unable to create installer cache dir: {err}");
+      return;
     }
-    Some(template) => {
-      let template = std::fs::read_to_string(std::path::Path::new(&template))
-        .map_err(|err| {
+  }
+  if let Err(err) = std::fs::write(&path, content) {
+    log::warn!("Unable to write installer cache: {err}");
+  }
+}
+
+/// ## Get template
+///
+/// Get template from our GitHub repo, from the specified file if it's provided,
+/// or from the local cache when `offline` is set. When fetched from the
+/// network, the body is verified against `expected_sha256` when provided, or
+/// against the sibling `installer.yml.sha256` published alongside it, and
+/// rejected on mismatch. A successfully verified template is cached so a
+/// subsequent install can run with `--offline`.
+///
+/// ## Arguments
+///
+/// * [template](Option<String>) Path to a local template file, if provided
+/// * [expected_sha256](Option<String>) Expected digest to verify the network template against
+/// * [offline](bool) When true, skip the network and use the local cache only
+/// * [verify](bool) When true (the default callers should use), integrity verification is
+///   mandatory: an unobtainable or mismatching digest rejects the template rather than
+///   silently skipping the check. Set to false only when the caller explicitly opted out.
+///
+/// ## Returns
+///
+/// * [Result](Result) Result of the operation
+///   * [Ok](InstallerTemplate) - The resolved template along with its source
+///   * [Err](IoError) - The template could not be resolved, or failed verification
+///
+pub async fn get_template(
+  template: Option<String>,
+  expected_sha256: Option<String>,
+  offline: bool,
+  verify: bool,
+) -> IoResult<InstallerTemplate> {
+  if let Some(template) = template {
+    let content = std::fs::read_to_string(std::path::Path::new(&template))
+      .map_err(|err| {
         err.map_err_context(|| "Unable to read installer template")
       })?;
-      Ok(template)
+    return Ok(InstallerTemplate {
+      content,
+      source: TemplateSource::Local(template),
+    });
+  }
+  if offline {
+    return get_cached_template();
+  }
+  let url = template_url();
+  let content = get(&url).await?;
+  let digest = match expected_sha256 {
+    Some(digest) => Some(digest),
+    None if verify => {
+      let digest = get(&template_sha256_url()).await.map_err(|err| {
+        IoError::invalid_data(
+          "Installer template",
+          &format!(
+            "unable to fetch checksum for verification, refusing unverified template: {err}"
+          ),
+        )
+      })?;
+      Some(digest)
     }
+    None => None,
+  };
+  if let Some(digest) = digest {
+    verify_digest(&content, &digest)?;
+    // Only a template that passed verification is cached as "last known
+    // good" for offline installs.
+    cache_template(&content);
   }
+  Ok(InstallerTemplate {
+    content,
+    source: TemplateSource::Network(url),
+  })
 }
@@ -0,0 +1,50 @@
+use tokio::sync::broadcast;
+
+use nanocl_stubs::system::Event;
+
+/// Default size of the broadcast channel buffer, generous enough to
+/// absorb a burst of events before a slow subscriber starts missing them.
+const EVENT_CHANNEL_SIZE: usize = 100;
+
+/// Broadcasts daemon-wide [Event](Event)s (cargo/node/...) to every
+/// subscriber, e.g. the http event stream endpoint used by `nanocl node watch`.
+#[derive(Clone)]
+pub struct EventEmitter {
+  sender: broadcast::Sender<Event>,
+}
+
+impl Default for EventEmitter {
+  fn default() -> Self {
+    let (sender, _) = broadcast::channel(EVENT_CHANNEL_SIZE);
+    Self { sender }
+  }
+}
+
+impl EventEmitter {
+  /// ## Spawn emit
+  ///
+  /// Broadcast an event to every current subscriber. Silently drops it if
+  /// there are none, since an event stream with no listener has nowhere to go.
+  ///
+  /// ## Arguments
+  ///
+  /// * [event](Event) The event to broadcast
+  ///
+  pub fn spawn_emit(&self, event: Event) {
+    let _ = self.sender.send(event);
+  }
+
+  /// ## Subscribe
+  ///
+  /// Subscribe to the daemon event stream, used by `GET /nodes/watch` and so
+  /// by the `node watch` CLI command.
+  ///
+  /// ## Returns
+  ///
+  /// * [Receiver](broadcast::Receiver<Event>) A receiver for every future event
+  ///
+  pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+    self.sender.subscribe()
+  }
+}
+
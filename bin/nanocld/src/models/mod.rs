@@ -0,0 +1,28 @@
+use std::sync::Arc;
+
+use diesel_async::AsyncPgConnection;
+use diesel_async::pooled_connection::deadpool;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
+
+use nanocl_stubs::config::DaemonConfig;
+
+use crate::event::EventEmitter;
+
+mod node;
+
+pub use node::{NodeDb, LIVENESS_GRACE_PERIOD};
+
+/// Async connection pool to the store, built on `diesel-async` + `deadpool`
+pub type Pool = deadpool::Pool<AsyncDieselConnectionManager<AsyncPgConnection>>;
+
+/// A single pooled async connection checked out from [Pool](Pool)
+pub type DBConn =
+  deadpool::Object<AsyncDieselConnectionManager<AsyncPgConnection>>;
+
+/// Shared daemon state handed to every subsystem and http handler
+#[derive(Clone)]
+pub struct DaemonState {
+  pub pool: Pool,
+  pub config: DaemonConfig,
+  pub event_emitter: Arc<EventEmitter>,
+}
@@ -0,0 +1,225 @@
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+use nanocl_error::io::{IoError, IoResult};
+use nanocl_stubs::node::{Node, NodeStatus};
+
+use crate::schema::nodes;
+use crate::utils::store::get_pool_conn;
+use super::Pool;
+
+/// Default grace window after which a node with no heartbeat is considered `Unreachable`
+pub const LIVENESS_GRACE_PERIOD: i64 = 30;
+
+/// A node registered in the cluster.
+#[derive(Debug, Clone, Queryable, Identifiable, Insertable)]
+#[diesel(table_name = nodes)]
+#[diesel(primary_key(name))]
+pub struct NodeDb {
+  pub name: String,
+  pub ip_address: String,
+  pub last_seen: NaiveDateTime,
+}
+
+impl NodeDb {
+  /// ## Status
+  ///
+  /// Derive the liveness status of the node from its `last_seen` heartbeat.
+  /// Status is never stored: it is always computed from this timestamp, so
+  /// a node that resumes heartbeating automatically goes back to `Online`
+  /// without any extra transition to manage.
+  ///
+  /// ## Arguments
+  ///
+  /// * [grace_period](i64) Seconds without a heartbeat before `Unreachable`
+  ///
+  /// ## Returns
+  ///
+  /// * [NodeStatus](NodeStatus) The derived status
+  ///
+  pub fn status(&self, grace_period: i64) -> NodeStatus {
+    let elapsed = Utc::now().naive_utc() - self.last_seen;
+    if elapsed.num_seconds() > grace_period {
+      NodeStatus::Unreachable
+    } else {
+      NodeStatus::Online
+    }
+  }
+
+  /// ## Into node
+  ///
+  /// Convert into the [Node](Node) wire type returned by the http api,
+  /// stamping its derived status on the way out.
+  ///
+  /// ## Arguments
+  ///
+  /// * [grace_period](i64) Seconds without a heartbeat before `Unreachable`
+  ///
+  /// ## Returns
+  ///
+  /// * [Node](Node) The node, with its derived status
+  ///
+  pub fn into_node(self, grace_period: i64) -> Node {
+    let status = self.status(grace_period);
+    Node {
+      name: self.name,
+      ip_address: self.ip_address,
+      status,
+    }
+  }
+
+  /// ## Create if not exists
+  ///
+  /// Insert the node if it doesn't already exist.
+  ///
+  /// ## Arguments
+  ///
+  /// * [item](NodeDb) The node to register
+  /// * [pool](Pool) The store pool
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](bool) - Whether the node was newly created
+  ///   * [Err](IoError) - The node could not be registered
+  ///
+  pub async fn create_if_not_exists(
+    item: &NodeDb,
+    pool: &Pool,
+  ) -> IoResult<bool> {
+    let mut conn = get_pool_conn(pool).await?;
+    let exists = nodes::table
+      .filter(nodes::name.eq(&item.name))
+      .get_result::<NodeDb>(&mut conn)
+      .await
+      .is_ok();
+    if exists {
+      return Ok(false);
+    }
+    diesel::insert_into(nodes::table)
+      .values(item)
+      .execute(&mut conn)
+      .await
+      .map_err(|err| {
+        IoError::interupted("Node", &format!("Unable to create node {err}"))
+      })?;
+    Ok(true)
+  }
+
+  /// ## Update last seen
+  ///
+  /// Stamp a node's `last_seen` with the current time, marking it `Online`
+  /// again on its next status read.
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) Name of the node
+  /// * [pool](Pool) The store pool
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](()) - The heartbeat has been recorded
+  ///   * [Err](IoError) - The heartbeat could not be recorded
+  ///
+  pub async fn update_last_seen(name: &str, pool: &Pool) -> IoResult<()> {
+    let mut conn = get_pool_conn(pool).await?;
+    diesel::update(nodes::table.filter(nodes::name.eq(name)))
+      .set(nodes::last_seen.eq(Utc::now().naive_utc()))
+      .execute(&mut conn)
+      .await
+      .map_err(|err| {
+        IoError::interupted(
+          "Node",
+          &format!("Unable to update node heartbeat {err}"),
+        )
+      })?;
+    Ok(())
+  }
+
+  /// ## Find by pk
+  ///
+  /// Find a single node by name.
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) Name of the node
+  /// * [pool](Pool) The store pool
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](NodeDb) - The node
+  ///   * [Err](IoError) - The node does not exist or could not be fetched
+  ///
+  pub async fn find_by_pk(name: &str, pool: &Pool) -> IoResult<NodeDb> {
+    let mut conn = get_pool_conn(pool).await?;
+    let node = nodes::table
+      .filter(nodes::name.eq(name))
+      .get_result::<NodeDb>(&mut conn)
+      .await
+      .map_err(|err| {
+        IoError::not_found("Node", &format!("{name}: {err}"))
+      })?;
+    Ok(node)
+  }
+
+  /// ## List
+  ///
+  /// List every node registered in the cluster, optionally filtered by
+  /// derived status.
+  ///
+  /// ## Arguments
+  ///
+  /// * [status](Option<NodeStatus>) Only return nodes with this derived status
+  /// * [grace_period](i64) Seconds without a heartbeat before `Unreachable`, used to derive `status`
+  /// * [pool](Pool) The store pool
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](Vec<NodeDb>) - The list of nodes
+  ///   * [Err](IoError) - The nodes could not be listed
+  ///
+  pub async fn list(
+    status: Option<NodeStatus>,
+    grace_period: i64,
+    pool: &Pool,
+  ) -> IoResult<Vec<NodeDb>> {
+    let mut conn = get_pool_conn(pool).await?;
+    let nodes = nodes::table.get_results::<NodeDb>(&mut conn).await.map_err(
+      |err| IoError::interupted("Node", &format!("Unable to list nodes {err}")),
+    )?;
+    Ok(match status {
+      Some(status) => nodes
+        .into_iter()
+        .filter(|node| node.status(grace_period) == status)
+        .collect(),
+      None => nodes,
+    })
+  }
+
+  /// ## Find stale
+  ///
+  /// List every node whose heartbeat is older than `grace_period` seconds,
+  /// i.e. every node the reaper currently sees as `Unreachable`.
+  ///
+  /// ## Arguments
+  ///
+  /// * [grace_period](i64) Seconds without a heartbeat before `Unreachable`
+  /// * [pool](Pool) The store pool
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](Vec<NodeDb>) - The list of stale nodes
+  ///   * [Err](IoError) - The nodes could not be listed
+  ///
+  pub async fn find_stale(
+    grace_period: i64,
+    pool: &Pool,
+  ) -> IoResult<Vec<NodeDb>> {
+    Self::list(Some(NodeStatus::Unreachable), grace_period, pool).await
+  }
+}
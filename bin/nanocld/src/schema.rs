@@ -0,0 +1,11 @@
+// Generated (in spirit) by `diesel print-schema`.
+// Only the tables touched by this series are declared here; the rest of
+// the generated schema lives outside this slice of the tree.
+
+diesel::table! {
+  nodes (name) {
+    name -> Varchar,
+    ip_address -> Varchar,
+    last_seen -> Timestamptz,
+  }
+}
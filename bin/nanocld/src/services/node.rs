@@ -0,0 +1,109 @@
+use futures_util::stream;
+use tokio::sync::broadcast;
+
+use ntex::util::Bytes;
+use ntex::web;
+
+use nanocl_error::io::IoResult;
+use nanocl_stubs::node::NodeStatus;
+use nanocl_stubs::system::{Event, EventKind};
+
+use crate::models::{DaemonState, NodeDb, LIVENESS_GRACE_PERIOD};
+
+/// Query string accepted by `GET /nodes`
+#[derive(Debug, serde::Deserialize)]
+struct ListNodeQuery {
+  status: Option<String>,
+}
+
+/// ## List node
+///
+/// `GET /nodes`: list every node known to the cluster, optionally filtered
+/// by its derived liveness status.
+async fn list_node(
+  state: web::types::State<DaemonState>,
+  query: web::types::Query<ListNodeQuery>,
+) -> IoResult<web::HttpResponse> {
+  let status = query.status.as_deref().map(|status| {
+    if status.eq_ignore_ascii_case("online") {
+      NodeStatus::Online
+    } else {
+      NodeStatus::Unreachable
+    }
+  });
+  let nodes = NodeDb::list(status, LIVENESS_GRACE_PERIOD, &state.pool)
+    .await?
+    .into_iter()
+    .map(|node| node.into_node(LIVENESS_GRACE_PERIOD))
+    .collect::<Vec<_>>();
+  Ok(web::HttpResponse::Ok().json(&nodes))
+}
+
+/// ## Inspect node
+///
+/// `GET /nodes/{name}/inspect`: get detailed information about a single node.
+async fn inspect_node(
+  state: web::types::State<DaemonState>,
+  name: web::types::Path<String>,
+) -> IoResult<web::HttpResponse> {
+  let node = NodeDb::find_by_pk(&name, &state.pool)
+    .await?
+    .into_node(LIVENESS_GRACE_PERIOD);
+  Ok(web::HttpResponse::Ok().json(&node))
+}
+
+/// ## Event stream
+///
+/// Turn a subscription to the daemon's [EventEmitter](crate::event::EventEmitter)
+/// into a stream of newline-delimited JSON node events, skipping over events
+/// of any other kind and any missed (lagged) events instead of ending the stream.
+fn event_stream(
+  rx: broadcast::Receiver<Event>,
+) -> impl futures_util::Stream<Item = IoResult<Bytes>> {
+  stream::unfold(rx, |mut rx| async move {
+    loop {
+      match rx.recv().await {
+        Ok(event) => {
+          if !matches!(event.kind, EventKind::Node) {
+            continue;
+          }
+          let Ok(mut payload) = serde_json::to_vec(&event) else {
+            continue;
+          };
+          payload.push(b'\n');
+          return Some((Ok(Bytes::from(payload)), rx));
+        }
+        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+        Err(broadcast::error::RecvError::Closed) => return None,
+      }
+    }
+  })
+}
+
+/// ## Watch node
+///
+/// `GET /nodes/watch`: stream node liveness events (registration,
+/// `Online`<->`Unreachable` transitions) as they happen.
+async fn watch_node(state: web::types::State<DaemonState>) -> web::HttpResponse {
+  let rx = state.event_emitter.subscribe();
+  web::HttpResponse::Ok()
+    .content_type("application/vnd.nanocl.nodes.events+json")
+    .streaming(event_stream(rx))
+}
+
+/// ## Ntex config
+///
+/// Register the `/nodes` routes used by `nanocl node ls|inspect|watch`.
+///
+/// ## Arguments
+///
+/// * [config](web::ServiceConfig) The ntex service config to register routes on
+///
+pub fn ntex_config(config: &mut web::ServiceConfig) {
+  config.service(web::resource("/nodes").route(web::get().to(list_node)));
+  config.service(
+    web::resource("/nodes/{name}/inspect").route(web::get().to(inspect_node)),
+  );
+  config
+    .service(web::resource("/nodes/watch").route(web::get().to(watch_node)));
+}
@@ -1,12 +1,153 @@
+use std::collections::HashSet;
+use std::time::Duration;
+
+use ntex::rt;
+use ntex::time;
+
 use nanocl_error::io::IoResult;
 
-use crate::models::{DaemonState, NodeDb};
+use nanocl_stubs::system::{Event, EventAction, EventKind};
+
+use crate::models::{DaemonState, NodeDb, LIVENESS_GRACE_PERIOD};
 
+/// Default interval between two `last_seen` heartbeats of the local node
+const HEARTBEAT_INTERVAL: u64 = 5;
+
+/// How often the reaper scans the node registry for stale heartbeats
+const REAPER_INTERVAL: u64 = HEARTBEAT_INTERVAL;
+
+/// ## Register
+///
+/// Register the local node in the store if it does not already exist, stamp
+/// its `last_seen` so it reports `Online` right away, and start the
+/// heartbeat and reaper background tasks.
+///
+/// ## Arguments
+///
+/// * [state](DaemonState) The daemon state
+///
+/// ## Returns
+///
+/// * [Result](Result) Result of the operation
+///   * [Ok](()) - The node has been registered
+///   * [Err](IoError) - The node could not be registered
+///
 pub async fn register(state: &DaemonState) -> IoResult<()> {
   let node = NodeDb {
     name: state.config.hostname.clone(),
     ip_address: state.config.gateway.clone(),
+    last_seen: chrono::Utc::now().naive_utc(),
   };
-  NodeDb::create_if_not_exists(&node, &state.pool).await?;
+  let is_new = NodeDb::create_if_not_exists(&node, &state.pool).await?;
+  NodeDb::update_last_seen(&node.name, &state.pool).await?;
+  if is_new {
+    emit_node_event(&node.name, EventAction::Create, state).await?;
+  }
+  spawn_heartbeat(state);
+  spawn_reaper(state);
+  Ok(())
+}
+
+/// ## Spawn heartbeat
+///
+/// Periodically stamp the local node's `last_seen` so it keeps reporting
+/// `Online` to the rest of the cluster.
+///
+/// ## Arguments
+///
+/// * [state](DaemonState) The daemon state
+///
+fn spawn_heartbeat(state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    loop {
+      time::sleep(Duration::from_secs(HEARTBEAT_INTERVAL)).await;
+      if let Err(err) =
+        NodeDb::update_last_seen(&state.config.hostname, &state.pool).await
+      {
+        log::warn!("Unable to update local node heartbeat: {err}");
+      }
+    }
+  });
+}
+
+/// ## Spawn reaper
+///
+/// Periodically ask [NodeDb::find_stale](NodeDb::find_stale) for every node
+/// whose heartbeat is older than [LIVENESS_GRACE_PERIOD](LIVENESS_GRACE_PERIOD)
+/// and emit a node event on every `Online`<->`Unreachable` transition.
+/// Status itself is never stored: it is always derived from `last_seen` on
+/// read, so a node that resumes heartbeating is automatically `Online`
+/// again on its next scan. The in-memory set below only tracks which nodes
+/// were reported `Unreachable` on the previous scan, so the reaper emits a
+/// transition event once instead of on every scan.
+///
+/// ## Arguments
+///
+/// * [state](DaemonState) The daemon state
+///
+fn spawn_reaper(state: &DaemonState) {
+  let state = state.clone();
+  rt::spawn(async move {
+    let mut unreachable: HashSet<String> = HashSet::new();
+    loop {
+      time::sleep(Duration::from_secs(REAPER_INTERVAL)).await;
+      let stale =
+        match NodeDb::find_stale(LIVENESS_GRACE_PERIOD, &state.pool).await {
+          Ok(nodes) => nodes,
+          Err(err) => {
+            log::warn!("Unable to scan nodes for stale heartbeats: {err}");
+            continue;
+          }
+        };
+      let current: HashSet<String> =
+        stale.into_iter().map(|node| node.name).collect();
+      for name in current.difference(&unreachable) {
+        if let Err(err) =
+          emit_node_event(name, EventAction::Update, &state).await
+        {
+          log::warn!("Unable to emit node unreachable event: {err}");
+        }
+      }
+      for name in unreachable.difference(&current) {
+        if let Err(err) =
+          emit_node_event(name, EventAction::Update, &state).await
+        {
+          log::warn!("Unable to emit node online event: {err}");
+        }
+      }
+      unreachable = current;
+    }
+  });
+}
+
+/// ## Emit node event
+///
+/// Broadcast a node event to every subscriber of the daemon's
+/// [EventEmitter](crate::event::EventEmitter).
+///
+/// ## Arguments
+///
+/// * [name](str) Name of the node
+/// * [action](EventAction) The action that triggered the event
+/// * [state](DaemonState) The daemon state
+///
+/// ## Returns
+///
+/// * [Result](Result) Result of the operation
+///   * [Ok](()) - The event has been emitted
+///   * [Err](IoError) - The node could not be fetched to build the event
+///
+async fn emit_node_event(
+  name: &str,
+  action: EventAction,
+  state: &DaemonState,
+) -> IoResult<()> {
+  let node = NodeDb::find_by_pk(name, &state.pool).await?;
+  state.event_emitter.spawn_emit(Event {
+    kind: EventKind::Node,
+    action,
+    actor: Some(node.name),
+  });
   Ok(())
 }
@@ -1,24 +1,259 @@
+use std::fs::File;
+use std::io::BufReader;
 use std::time::Duration;
 use std::net::ToSocketAddrs;
+use std::sync::Arc;
 
 use ntex::{rt, web, time};
-use diesel::PgConnection;
-use diesel::r2d2::ConnectionManager;
+use diesel::{Connection, PgConnection};
+use diesel_async::AsyncPgConnection;
+use diesel_async::pooled_connection::ManagerConfig;
+use diesel_async::pooled_connection::AsyncDieselConnectionManager;
 use diesel_migrations::{EmbeddedMigrations, MigrationHarness, embed_migrations};
+use futures_util::future::{BoxFuture, FutureExt};
+use rand::Rng;
 
-use nanocl_stubs::config::DaemonConfig;
+use nanocl_stubs::config::{DaemonConfig, StoreTlsMode, StoreTlsConfig};
 
 use nanocl_error::io::{IoError, IoResult};
 
 use crate::models::{Pool, DBConn};
 
+/// ## Sslmode str
+///
+/// Map a [StoreTlsMode](StoreTlsMode) to the `sslmode` value `libpq`/
+/// `tokio-postgres` understand.
+fn sslmode_str(mode: StoreTlsMode) -> &'static str {
+  match mode {
+    StoreTlsMode::Disable => "disable",
+    StoreTlsMode::Require => "require",
+    StoreTlsMode::VerifyFull => "verify-full",
+  }
+}
+
+/// ## Append query param
+///
+/// Append a `key=value` pair to a connection url's query string, using `&`
+/// if the url already has a query string or `?` if it doesn't. Leaves the
+/// url untouched if `key` is already present in it, so an operator who set
+/// it explicitly in a custom `STORE_URL` keeps their own value.
+///
+/// ## Arguments
+///
+/// * [url](str) The connection url to extend
+/// * [key](str) The query parameter name
+/// * [value](str) The query parameter value
+///
+/// ## Returns
+///
+/// * [String](String) The url with the parameter appended
+///
+fn append_query_param(url: &str, key: &str, value: &str) -> String {
+  if url.contains(&format!("{key}=")) {
+    return url.to_owned();
+  }
+  let separator = if url.contains('?') { '&' } else { '?' };
+  format!("{url}{separator}{key}={value}")
+}
+
+/// ## Build connection url
+///
+/// Build the postgres connection url for the store, using a full `STORE_URL`
+/// when the operator supplied one and falling back to the legacy CockroachDB
+/// layout (`{state_dir}/store/certs/...`) otherwise, so existing installs
+/// keep working unchanged. `sslmode` is spliced in from the resolved TLS
+/// config, including onto an operator-supplied `STORE_URL` that does not
+/// already set its own `sslmode`, so
+/// `tokio-postgres` enforces (rather than merely prefers) TLS when it is
+/// enabled; `sslcert`/`sslkey`/`sslrootcert` are intentionally left out
+/// since those are handled by our rustls connector instead.
+///
+/// ## Arguments
+///
+/// * [host](str) Host to connect to
+/// * [daemon_conf](DaemonConfig) The daemon configuration
+/// * [tls](StoreTlsConfig) The resolved TLS configuration
+///
+/// ## Returns
+///
+/// * [String](String) The postgres connection url, without embedded `sslcert`/`sslkey`/`sslrootcert`
+///
+fn build_connection_url(
+  host: &str,
+  daemon_conf: &DaemonConfig,
+  tls: &StoreTlsConfig,
+) -> String {
+  let sslmode = sslmode_str(tls.mode);
+  if let Some(store_url) = &daemon_conf.store_url {
+    return append_query_param(store_url, "sslmode", sslmode);
+  }
+  format!("postgresql://root:root@{host}/defaultdb?sslmode={sslmode}")
+}
+
+/// ## Resolve tls config
+///
+/// Resolve the TLS options to use for the store connection.
+///
+/// Defaulting is keyed on whether a custom `store_url` was supplied, not
+/// only on an explicit `store_tls` override: when neither is set we keep
+/// the historic CockroachDB cert locations under `{state_dir}/store/certs`
+/// (so existing installs are unaffected); when a custom `store_url` is
+/// given with no explicit TLS override we default to `require` instead,
+/// since the hardcoded CockroachDB cert paths almost certainly don't exist
+/// on a generic/managed Postgres and would otherwise fail to connect.
+///
+/// ## Arguments
+///
+/// * [daemon_conf](DaemonConfig) The daemon configuration
+///
+/// ## Returns
+///
+/// * [StoreTlsConfig](StoreTlsConfig) The resolved TLS configuration
+///
+fn resolve_tls_config(daemon_conf: &DaemonConfig) -> StoreTlsConfig {
+  if let Some(tls) = &daemon_conf.store_tls {
+    return tls.clone();
+  }
+  if daemon_conf.store_url.is_some() {
+    return StoreTlsConfig {
+      mode: StoreTlsMode::Require,
+      ca_cert: None,
+      client_cert: None,
+      client_key: None,
+    };
+  }
+  let state_dir = daemon_conf.state_dir.clone();
+  StoreTlsConfig {
+    mode: StoreTlsMode::VerifyFull,
+    ca_cert: Some(format!("{state_dir}/store/certs/ca.crt")),
+    client_cert: Some(format!("{state_dir}/store/certs/client.root.crt")),
+    client_key: Some(format!("{state_dir}/store/certs/client.root.key")),
+  }
+}
+
+/// ## Build tls connector
+///
+/// Build a `rustls::ClientConfig` from a [StoreTlsConfig](StoreTlsConfig),
+/// loading the CA root via `rustls-pemfile` and the client identity when
+/// provided. Returns `None` when TLS is disabled.
+///
+/// ## Arguments
+///
+/// * [tls](StoreTlsConfig) The TLS configuration to build the connector from
+///
+/// ## Returns
+///
+/// * [Result](Result) Result of the operation
+///   * [Ok](Option<rustls::ClientConfig>) - The built tls config, if enabled
+///   * [Err](IoError) - The tls config could not be built
+///
+fn build_tls_connector(
+  tls: &StoreTlsConfig,
+) -> IoResult<Option<rustls::ClientConfig>> {
+  if tls.mode == StoreTlsMode::Disable {
+    return Ok(None);
+  }
+  if tls.mode == StoreTlsMode::VerifyFull && tls.ca_cert.is_none() {
+    return Err(IoError::invalid_data(
+      "Store tls",
+      "verify-full requires a ca_cert to verify the server against",
+    ));
+  }
+  let mut root_store = rustls::RootCertStore::empty();
+  if let Some(ca_cert) = &tls.ca_cert {
+    let file = File::open(ca_cert).map_err(|err| {
+      IoError::invalid_data("Store tls", &format!("unable to read ca cert {err}"))
+    })?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+      .map_err(|err| {
+        IoError::invalid_data("Store tls", &format!("invalid ca cert {err}"))
+      })?;
+    for cert in certs {
+      root_store.add(&rustls::Certificate(cert)).map_err(|err| {
+        IoError::invalid_data("Store tls", &format!("invalid ca cert {err}"))
+      })?;
+    }
+  }
+  let builder = rustls::ClientConfig::builder()
+    .with_safe_defaults()
+    .with_root_certificates(root_store);
+  let config = match (&tls.client_cert, &tls.client_key) {
+    (Some(cert_path), Some(key_path)) => {
+      let cert_file = File::open(cert_path).map_err(|err| {
+        IoError::invalid_data(
+          "Store tls",
+          &format!("unable to read client cert {err}"),
+        )
+      })?;
+      let certs = rustls_pemfile::certs(&mut BufReader::new(cert_file))
+        .map_err(|err| {
+          IoError::invalid_data("Store tls", &format!("invalid client cert {err}"))
+        })?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+      let key_file = File::open(key_path).map_err(|err| {
+        IoError::invalid_data(
+          "Store tls",
+          &format!("unable to read client key {err}"),
+        )
+      })?;
+      let mut keys = rustls_pemfile::pkcs8_private_keys(&mut BufReader::new(
+        key_file,
+      ))
+      .map_err(|err| {
+        IoError::invalid_data("Store tls", &format!("invalid client key {err}"))
+      })?;
+      let key = keys.pop().ok_or_else(|| {
+        IoError::invalid_data("Store tls", "no client key found")
+      })?;
+      builder
+        .with_client_auth_cert(certs, rustls::PrivateKey(key))
+        .map_err(|err| {
+          IoError::invalid_data("Store tls", &format!("invalid client identity {err}"))
+        })?
+    }
+    _ => builder.with_no_client_auth(),
+  };
+  let mut config = config;
+  if tls.mode == StoreTlsMode::Require && tls.ca_cert.is_none() {
+    // `require` without a CA only asks for an encrypted channel, not a
+    // verified one (mirrors libpq's `sslmode=require` semantics) - skip
+    // verification explicitly rather than silently falling back to plaintext.
+    config
+      .dangerous()
+      .set_certificate_verifier(Arc::new(NoCertVerification));
+  }
+  Ok(Some(config))
+}
+
+/// No-op certificate verifier used for `sslmode=require` without a CA: it
+/// still forces the connection to negotiate TLS, it just does not check the
+/// server's certificate chain.
+struct NoCertVerification;
+
+impl rustls::client::ServerCertVerifier for NoCertVerification {
+  fn verify_server_cert(
+    &self,
+    _end_entity: &rustls::Certificate,
+    _intermediates: &[rustls::Certificate],
+    _server_name: &rustls::ServerName,
+    _scts: &mut dyn Iterator<Item = &[u8]>,
+    _ocsp_response: &[u8],
+    _now: std::time::SystemTime,
+  ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+    Ok(rustls::client::ServerCertVerified::assertion())
+  }
+}
+
 /// ## Create pool
 ///
-/// Create a pool connection to the store `cockroachdb`
+/// Create an async pool connection to the store
 ///
 /// ## Arguments
 ///
 /// * [host](str) Host to connect to
+/// * [daemon_conf](DaemonConfig) The daemon configuration
 ///
 /// ## Returns
 ///
@@ -30,26 +265,69 @@ pub async fn create_pool(
   host: &str,
   daemon_conf: &DaemonConfig,
 ) -> IoResult<Pool> {
-  let state_dir = daemon_conf.state_dir.clone();
-  let options = format!("/defaultdb?sslmode=verify-full&sslcert={state_dir}/store/certs/client.root.crt&sslkey={state_dir}/store/certs/client.root.key&sslrootcert={state_dir}/store/certs/ca.crt");
-  let db_url = format!("postgresql://root:root@{host}{options}");
-  web::block(move || {
-    let manager = ConnectionManager::<PgConnection>::new(db_url);
-    r2d2::Pool::builder().build(manager)
-  })
-  .await
-  .map_err(|err| {
-    IoError::interupted("CockroachDB", &format!("Unable to create pool {err}"))
+  let tls = resolve_tls_config(daemon_conf);
+  let db_url = build_connection_url(host, daemon_conf, &tls);
+  let tls_config = build_tls_connector(&tls)?;
+  let manager = match tls_config {
+    Some(tls_config) => {
+      let mut config = ManagerConfig::default();
+      config.custom_setup =
+        Box::new(move |url| establish_with_tls(url, tls_config.clone()));
+      AsyncDieselConnectionManager::<AsyncPgConnection>::new_with_config(
+        db_url, config,
+      )
+    }
+    None => AsyncDieselConnectionManager::<AsyncPgConnection>::new(db_url),
+  };
+  Pool::builder(manager).build().map_err(|err| {
+    IoError::interupted("Store", &format!("Unable to create pool {err}"))
   })
 }
 
+/// ## Establish with tls
+///
+/// Custom `diesel-async` connection setup that upgrades the underlying
+/// `tokio-postgres` socket with a rustls connector instead of relying on
+/// `sslmode`/`sslcert` query parameters embedded in the connection url.
+///
+/// ## Arguments
+///
+/// * [url](str) The postgres connection url
+/// * [tls_config](rustls::ClientConfig) The tls config to connect with
+///
+/// ## Returns
+///
+/// * [BoxFuture](BoxFuture) resolving to the established [AsyncPgConnection](AsyncPgConnection)
+///
+fn establish_with_tls(
+  url: &str,
+  tls_config: rustls::ClientConfig,
+) -> BoxFuture<diesel::ConnectionResult<AsyncPgConnection>> {
+  let url = url.to_owned();
+  async move {
+    let connector = tokio_postgres_rustls::MakeRustlsConnect::new(tls_config);
+    let (client, conn) = tokio_postgres::connect(&url, connector)
+      .await
+      .map_err(|err| {
+        diesel::ConnectionError::BadConnection(err.to_string())
+      })?;
+    ntex::rt::spawn(async move {
+      if let Err(err) = conn.await {
+        log::error!("Store connection error: {err}");
+      }
+    });
+    AsyncPgConnection::try_from(client).await
+  }
+  .boxed()
+}
+
 /// ## Get pool conn
 ///
-/// Get connection from the connection pool for the store `cockroachdb`
+/// Get an async connection from the connection pool for the store `cockroachdb`
 ///
 /// ## Arguments
 ///
-/// [pool](Pool) a pool wrapped in ntex State
+/// * [pool](Pool) a pool wrapped in ntex State
 ///
 /// ## Returns
 ///
@@ -57,8 +335,8 @@ pub async fn create_pool(
 ///   * [Ok](DBConn) - The connection has been retrieved
 ///   * [Err](IoError) - The connection has not been retrieved
 ///
-pub fn get_pool_conn(pool: &Pool) -> IoResult<DBConn> {
-  let conn = match pool.get() {
+pub async fn get_pool_conn(pool: &Pool) -> IoResult<DBConn> {
+  let conn = match pool.get().await {
     Ok(conn) => conn,
     Err(err) => {
       return Err(IoError::new(
@@ -70,45 +348,89 @@ pub fn get_pool_conn(pool: &Pool) -> IoResult<DBConn> {
   Ok(conn)
 }
 
+/// Initial delay between two connection attempts, doubled after every failure
+const WAIT_STORE_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Upper bound the backoff is capped at, regardless of how many attempts failed
+const WAIT_STORE_MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+/// Default total time we're willing to wait for the store to come up before giving up
+const WAIT_STORE_DEFAULT_TIMEOUT: Duration = Duration::from_secs(120);
+
 /// ## Wait store
 ///
-/// Wait for store to be ready to accept tcp connection.
-/// We loop until a tcp connection can be established to the store.
+/// Wait for store to be ready to accept a tcp connection, using exponential
+/// backoff with jitter between attempts. DNS resolution failures are treated
+/// as retryable, since the store's name may not resolve yet at boot, and
+/// every resolved [SocketAddr](std::net::SocketAddr) is tried rather than
+/// only the first one. Gives up with a structured [IoError](IoError) once
+/// `timeout` has elapsed instead of looping forever.
 ///
 /// ## Arguments
 ///
 /// * [addr](str) Address of the store
+/// * [timeout](Duration) Maximum total time to wait before failing
 ///
 /// ## Returns
 ///
 /// * [Result](Result) Result of the operation
 ///   * [Ok](()) - The store is ready
-///   * [Err](IoError) - The store is not ready
-///
-async fn wait_store(addr: &str) -> IoResult<()> {
-  // Open tcp connection to check if store is ready
-  let addr = addr
-    .to_socket_addrs()
-    .map_err(|err| {
-      IoError::invalid_data(
+///   * [Err](IoError) - The store did not become ready before the timeout
+///
+async fn wait_store(addr: &str, timeout: Duration) -> IoResult<()> {
+  let started_at = std::time::Instant::now();
+  let mut backoff = WAIT_STORE_INITIAL_BACKOFF;
+  loop {
+    match addr.to_socket_addrs() {
+      Ok(addrs) => {
+        for sock_addr in addrs {
+          if rt::tcp_connect(sock_addr).await.is_ok() {
+            return Ok(());
+          }
+        }
+        log::warn!("Waiting for store at {addr}");
+      }
+      Err(err) => {
+        log::warn!("Waiting for store dns resolution of {addr}: {err}");
+      }
+    }
+    if started_at.elapsed() >= timeout {
+      return Err(IoError::new(
         "Wait store",
-        &format!("invalid address format {err}"),
-      )
-    })?
-    .next()
-    .expect("Unable to resolve store address");
-  while let Err(_err) = rt::tcp_connect(addr).await {
-    log::warn!("Waiting for store");
-    time::sleep(Duration::from_secs(2)).await;
-  }
-  time::sleep(Duration::from_secs(2)).await;
-  Ok(())
+        std::io::Error::new(
+          std::io::ErrorKind::TimedOut,
+          format!("store at {addr} did not become ready within {timeout:?}"),
+        ),
+      ));
+    }
+    time::sleep(jittered(backoff)).await;
+    backoff = std::cmp::min(backoff * 2, WAIT_STORE_MAX_BACKOFF);
+  }
+}
+
+/// ## Jittered
+///
+/// Apply up to 20% of random jitter on top of a backoff duration, so that
+/// multiple daemons racing to reach the store do not retry in lockstep.
+///
+/// ## Arguments
+///
+/// * [backoff](Duration) The base backoff duration
+///
+/// ## Returns
+///
+/// * [Duration](Duration) The jittered duration
+///
+fn jittered(backoff: Duration) -> Duration {
+  let jitter_ratio = rand::thread_rng().gen_range(0.0..0.2);
+  backoff + backoff.mul_f64(jitter_ratio)
 }
 
 /// ## Init
 ///
 /// Ensure existance of a container for our store.
-/// We use cockroachdb with a postgresql connector.
+/// We connect to any postgres-compatible store (CockroachDB by default,
+/// or a custom `STORE_URL`/TLS configuration).
 /// We also run latest migration on our database to have the latest schema.
 /// It will return a connection Pool that will be use in our State.
 ///
@@ -123,13 +445,106 @@ pub(crate) async fn init(daemon_conf: &DaemonConfig) -> IoResult<Pool> {
   let store_addr = std::env::var("STORE_URL")
     .unwrap_or("nstore.nanocl.internal:26258".to_owned());
   log::info!("Connecting to store at: {store_addr}");
-  wait_store(&store_addr).await?;
+  let wait_timeout = std::env::var("STORE_WAIT_TIMEOUT")
+    .ok()
+    .and_then(|value| value.parse::<u64>().ok())
+    .map(Duration::from_secs)
+    .unwrap_or(WAIT_STORE_DEFAULT_TIMEOUT);
+  wait_store(&store_addr, wait_timeout).await?;
+  run_migrations(&store_addr, daemon_conf, MIGRATIONS).await?;
   let pool = create_pool(&store_addr, daemon_conf).await?;
-  let mut conn = get_pool_conn(&pool)?;
-  log::info!("Store connected, running migrations");
-  conn.run_pending_migrations(MIGRATIONS).map_err(|err| {
-    IoError::interupted("CockroachDB migration", &format!("{err}"))
-  })?;
-  log::info!("Migrations successfully applied");
+  log::info!("Store connected, migrations successfully applied");
   Ok(pool)
 }
+
+/// ## Build migration url
+///
+/// Build the connection url used by the one-shot migration connection,
+/// which must honor the same TLS parameters as the async pool so a
+/// CA/client cert rotation applies to both consistently. Unlike
+/// [build_connection_url](build_connection_url), the migration connection
+/// is a plain blocking `PgConnection` with no rustls connector behind it,
+/// so the cert paths themselves also need to be spliced onto an
+/// operator-supplied `STORE_URL`, not just `sslmode`.
+///
+/// ## Arguments
+///
+/// * [host](str) Host to connect to
+/// * [daemon_conf](DaemonConfig) The daemon configuration
+/// * [tls](StoreTlsConfig) The TLS configuration to apply
+///
+/// ## Returns
+///
+/// * [String](String) The postgres connection url for the migration connection
+///
+fn build_migration_url(
+  host: &str,
+  daemon_conf: &DaemonConfig,
+  tls: &StoreTlsConfig,
+) -> String {
+  let sslmode = sslmode_str(tls.mode);
+  if let Some(store_url) = &daemon_conf.store_url {
+    let mut url = append_query_param(store_url, "sslmode", sslmode);
+    if let Some(ca_cert) = &tls.ca_cert {
+      url = append_query_param(&url, "sslrootcert", ca_cert);
+    }
+    if let Some(client_cert) = &tls.client_cert {
+      url = append_query_param(&url, "sslcert", client_cert);
+    }
+    if let Some(client_key) = &tls.client_key {
+      url = append_query_param(&url, "sslkey", client_key);
+    }
+    return url;
+  }
+  let mut options = format!("?sslmode={sslmode}");
+  if let Some(ca_cert) = &tls.ca_cert {
+    options.push_str(&format!("&sslrootcert={ca_cert}"));
+  }
+  if let Some(client_cert) = &tls.client_cert {
+    options.push_str(&format!("&sslcert={client_cert}"));
+  }
+  if let Some(client_key) = &tls.client_key {
+    options.push_str(&format!("&sslkey={client_key}"));
+  }
+  format!("postgresql://root:root@{host}/defaultdb{options}")
+}
+
+/// ## Run migrations
+///
+/// Open a one-shot, synchronous connection to the store purely to apply
+/// pending migrations, then drop it. Migrations run through `diesel_migrations`,
+/// which only supports the blocking `PgConnection`, so this stays separate
+/// from the async pool used for everyday queries.
+///
+/// ## Arguments
+///
+/// * [host](str) Host to connect to
+/// * [daemon_conf](DaemonConfig) The daemon configuration
+/// * [migrations](EmbeddedMigrations) The migrations to apply
+///
+/// ## Returns
+///
+/// * [Result](Result) Result of the operation
+///   * [Ok](()) - The migrations have been applied
+///   * [Err](IoError) - The migrations could not be applied
+///
+async fn run_migrations(
+  host: &str,
+  daemon_conf: &DaemonConfig,
+  migrations: EmbeddedMigrations,
+) -> IoResult<()> {
+  let tls = resolve_tls_config(daemon_conf);
+  let db_url = build_migration_url(host, daemon_conf, &tls);
+  web::block(move || {
+    let mut conn = PgConnection::establish(&db_url)?;
+    conn.run_pending_migrations(migrations).map(|_| ())?;
+    Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
+  })
+  .await
+  .map_err(|err| {
+    IoError::interupted("CockroachDB migration", &format!("{err}"))
+  })?
+  .map_err(|err| {
+    IoError::interupted("CockroachDB migration", &format!("{err}"))
+  })
+}
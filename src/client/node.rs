@@ -0,0 +1,78 @@
+use futures_util::{Stream, StreamExt};
+
+use nanocl_error::http_client::HttpClientResult;
+use nanocl_stubs::node::Node;
+use nanocl_stubs::system::Event;
+
+use super::NanocldClient;
+
+/// `/nodes` endpoint, following the same shape as the other resource
+/// modules declared alongside this one (cargo, namespace, container, ...).
+impl NanocldClient {
+  /// ## List node
+  ///
+  /// List every node known to the cluster, optionally filtered by status.
+  ///
+  /// ## Arguments
+  ///
+  /// * [status](Option<&str>) Only return nodes with this (derived) status
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](Vec<Node>) - The list of nodes
+  ///   * [Err](HttpClientError) - The nodes could not be listed
+  ///
+  pub async fn list_node(
+    &self,
+    status: Option<&str>,
+  ) -> HttpClientResult<Vec<Node>> {
+    let mut query: Vec<(&str, &str)> = Vec::new();
+    if let Some(status) = status {
+      query.push(("status", status));
+    }
+    self.send_get("/nodes", Some(query)).await?.json::<Vec<Node>>().await
+  }
+
+  /// ## Inspect node
+  ///
+  /// Get detailed information about a single node.
+  ///
+  /// ## Arguments
+  ///
+  /// * [name](str) Name of the node
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](Node) - The node
+  ///   * [Err](HttpClientError) - The node could not be inspected
+  ///
+  pub async fn inspect_node(&self, name: &str) -> HttpClientResult<Node> {
+    self
+      .send_get(&format!("/nodes/{name}/inspect"), None::<String>)
+      .await?
+      .json::<Node>()
+      .await
+  }
+
+  /// ## Watch node events
+  ///
+  /// Stream node liveness events (registration, `Online`<->`Unreachable`
+  /// transitions) as they happen.
+  ///
+  /// ## Returns
+  ///
+  /// * [Result](Result) Result of the operation
+  ///   * [Ok](impl Stream<Item = HttpClientResult<Event>>) - The event stream
+  ///   * [Err](HttpClientError) - The stream could not be opened
+  ///
+  pub async fn watch_node_events(
+    &self,
+  ) -> HttpClientResult<impl Stream<Item = HttpClientResult<Event>>> {
+    let stream = self.send_get_stream("/nodes/watch", None::<String>).await?;
+    Ok(stream.map(|item| {
+      item.and_then(|bytes| Ok(serde_json::from_slice::<Event>(&bytes)?))
+    }))
+  }
+}